@@ -10,29 +10,83 @@ struct Volume {
     /// If this only contains a subset of the chapters, this indicates
     /// which chapters.
     chapter_range: Option<(usize, usize)>,
+
+    /// The book's あらすじ (synopsis/blurb), if any.
+    synopsis: String,
+
+    /// The book's genre/keyword tags, for the epub's `subject` metadata.
+    tags: Vec<String>,
+
+    /// The book's last-updated date, as shown on the page (not
+    /// necessarily a strict ISO date).
+    update_date: String,
+
+    /// A stable identifier for the book, e.g. `urn:ncode:n1234ab`.
+    identifier: String,
 }
 
 #[derive(Debug, Clone)]
 struct Chapter {
     title: String,
+
+    /// The chapter's heading + paragraphs, as a bare xhtml fragment (not
+    /// wrapped in a full `<html>` document). Used both to build a full
+    /// epub chapter page and, by the `markdown`/`html` output formats, as
+    /// the thing they convert/concatenate directly.
+    content_xhtml: String,
+
     xhtml_page: String,
 }
 
+/// How to obtain the epub's cover image, for `--cover`/`--no-cover`.
+#[derive(Debug, Clone)]
+enum CoverImage {
+    /// Render a simple typographic cover from the volume's title/subtitle/author.
+    Generated,
+
+    /// Use a user-supplied image file as-is.
+    Custom(std::path::PathBuf),
+}
+
+/// The subtitle plus, if this volume only covers a subset of chapters, a
+/// parenthetical chapter range, e.g. "　（3～10話）".
+fn composite_subtitle(volume: &Volume) -> String {
+    let mut sub = volume.subtitle.clone();
+    if !sub.is_empty() && volume.chapter_range.is_some() {
+        sub.push_str("　");
+    }
+    if let Some((start, end)) = volume.chapter_range {
+        sub.push_str(&format!("（{}～{}話）", start, end));
+    }
+    sub
+}
+
+/// Picks the best available zip backend: the external `zip` command
+/// produces noticeably smaller deflate output on large multi-hundred-
+/// chapter volumes, but isn't guaranteed to be installed, so we fall back
+/// to the bundled `ZipLibrary` when it can't be found.
+fn make_zip_backend() -> Box<dyn epub_builder::Zip> {
+    match epub_builder::ZipCommand::new() {
+        Ok(zip) if zip.test().is_ok() => Box::new(zip),
+        _ => Box::new(epub_builder::ZipLibrary::new().unwrap()),
+    }
+}
+
 /// (composite_subtitle, data)
-fn volume_to_epub(volume: &Volume, horizontal_text: bool) -> (String, Vec<u8>) {
-    let mut builder =
-        epub_builder::EpubBuilder::new(epub_builder::ZipLibrary::new().unwrap()).unwrap();
-
-    let composite_subtitle = {
-        let mut sub = volume.subtitle.clone();
-        if !sub.is_empty() && volume.chapter_range.is_some() {
-            sub.push_str("　");
-        }
-        if let Some((start, end)) = volume.chapter_range {
-            sub.push_str(&format!("（{}～{}話）", start, end));
-        }
-        sub
-    };
+fn volume_to_epub(
+    volume: &Volume,
+    horizontal_text: bool,
+    cover: Option<&CoverImage>,
+    epub_version: u8,
+) -> (String, Vec<u8>) {
+    let mut builder = epub_builder::EpubBuilder::new(make_zip_backend()).unwrap();
+    builder.epub_version(if epub_version == 2 {
+        epub_builder::EpubVersion::V20
+    } else {
+        epub_builder::EpubVersion::V30
+    });
+
+    let composite_subtitle = composite_subtitle(volume);
 
     let composite_title = {
         let mut t = volume.title.clone();
@@ -56,8 +110,68 @@ fn volume_to_epub(volume: &Volume, horizontal_text: bool) -> (String, Vec<u8>) {
     builder.set_lang("ja");
     builder.metadata("author", &volume.author).unwrap();
     builder.metadata("title", &composite_title).unwrap();
+    builder.metadata("identifier", &volume.identifier).unwrap();
+    if !volume.synopsis.is_empty() {
+        builder.metadata("description", &volume.synopsis).unwrap();
+    }
+    if !volume.update_date.is_empty() {
+        builder.metadata("date", &volume.update_date).unwrap();
+    }
+    for tag in &volume.tags {
+        builder.metadata("subject", tag).unwrap();
+    }
     builder.stylesheet(css.as_bytes()).unwrap();
 
+    // Adds an inline table-of-contents page to the book itself, listing
+    // every titled piece of content added below, for in-book navigation.
+    builder.inline_toc();
+
+    // Cover image, if any.
+    if let Some(cover) = cover {
+        let (cover_bytes, cover_filename, mime): (Vec<u8>, String, &str) = match cover {
+            CoverImage::Generated => (
+                generate_cover_svg(
+                    &ascii_to_fullwidth(&volume.title),
+                    &ascii_to_fullwidth(&composite_subtitle),
+                    &ascii_to_fullwidth(&volume.author),
+                    horizontal_text,
+                )
+                .into_bytes(),
+                "cover.svg".into(),
+                "image/svg+xml",
+            ),
+            CoverImage::Custom(path) => {
+                let bytes = std::fs::read(path).unwrap();
+                let filename = path
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                    .unwrap_or("cover.img")
+                    .to_string();
+                let mime = match path.extension().and_then(|e| e.to_str()) {
+                    Some("png") => "image/png",
+                    Some("jpg") | Some("jpeg") => "image/jpeg",
+                    Some("svg") => "image/svg+xml",
+                    _ => "image/png",
+                };
+                (bytes, filename, mime)
+            }
+        };
+
+        builder
+            .add_cover_image(&cover_filename, &cover_bytes[..], mime)
+            .unwrap();
+        builder
+            .add_content(
+                epub_builder::EpubContent::new(
+                    "cover.xhtml",
+                    cover_xhtml_page(&cover_filename).as_bytes(),
+                )
+                .title("Cover")
+                .reftype(epub_builder::ReferenceType::Cover),
+            )
+            .unwrap();
+    }
+
     // Title page.
     {
         let title = ascii_to_fullwidth(&volume.title);
@@ -85,6 +199,20 @@ fn volume_to_epub(volume: &Volume, horizontal_text: bool) -> (String, Vec<u8>) {
             .unwrap();
     }
 
+    // Synopsis page, if there is one.
+    if !volume.synopsis.is_empty() {
+        builder
+            .add_content(
+                epub_builder::EpubContent::new(
+                    "synopsis.xhtml",
+                    epub_synopsis_page(&ascii_to_fullwidth(&volume.title), &volume.synopsis)
+                        .as_bytes(),
+                )
+                .title("あらすじ"),
+            )
+            .unwrap();
+    }
+
     // Chapters in the volume.
     let mut is_first = true;
     for (chap_i, chapter) in volume.chapters.iter().enumerate() {
@@ -147,6 +275,230 @@ fn epub_title_page(title: &str, subtitle: Option<&str>, author: Option<&str>) ->
     page
 }
 
+fn epub_synopsis_page(title: &str, synopsis: &str) -> String {
+    let mut page = String::new();
+
+    page.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="ja" xml:lang="ja">
+<head>
+  <meta charset="utf-8" />
+"#);
+    page.push_str(&format!("<title>{}</title>\n", title));
+    page.push_str(
+        r#"
+  <link rel="stylesheet" type="text/css" href="stylesheet.css" />
+</head>
+<body epub:type="frontmatter">
+<section epub:type="synopsis" class="titlepage">
+"#,
+    );
+    page.push_str("<p>");
+    page.push_str(synopsis);
+    page.push_str("</p>\n");
+    page.push_str(
+        r#"</section>
+</body>
+</html>
+"#,
+    );
+
+    page
+}
+
+/// Renders a simple typographic cover as an SVG image, since the crate has
+/// no dependency on a raster-image library to draw a bitmap one.
+///
+/// Lays text out with plain `<text>` elements rather than
+/// `writing-mode` CSS inside a `foreignObject`: the cover-thumbnail
+/// pipelines of many readers (Kindle in particular, and several
+/// Kobo/calibre conversion paths) don't execute `foreignObject` content at
+/// all, which would leave the thumbnail blank.
+fn generate_cover_svg(title: &str, subtitle: &str, author: &str, horizontal_text: bool) -> String {
+    let mut svg = String::new();
+    svg.push_str(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" width="1200" height="1600" viewBox="0 0 1200 1600">
+<rect width="1200" height="1600" fill="#ffffff" />
+"#,
+    );
+
+    if horizontal_text {
+        let (text, next_y) = wrap_horizontal_text_svg(title, 600, 400, 900, 64, 20, 3, 1000);
+        svg.push_str(&text);
+        let next_y = if !subtitle.is_empty() {
+            let (text, next_y) =
+                wrap_horizontal_text_svg(subtitle, 600, next_y + 20, 1050, 36, 12, 2, 1000);
+            svg.push_str(&text);
+            next_y
+        } else {
+            next_y
+        };
+        if !author.is_empty() {
+            let y_start = (next_y + 40).max(1450).min(1550);
+            let (text, _) = wrap_horizontal_text_svg(author, 600, y_start, 1580, 32, 12, 1, 1000);
+            svg.push_str(&text);
+        }
+    } else {
+        // Columns are laid out right-to-left, one glyph per row, to
+        // approximate vertical Japanese text without relying on CSS
+        // writing modes. Font size shrinks and the text wraps into
+        // further columns, rather than overflowing the canvas or
+        // overlapping the next field, when it's too long to fit.
+        let (text, next_x) = wrap_vertical_text_svg(title, 1080, 150, 950, 64, 24, 3);
+        svg.push_str(&text);
+        if !subtitle.is_empty() {
+            let (text, _) = wrap_vertical_text_svg(subtitle, next_x - 24, 150, 950, 36, 24, 2);
+            svg.push_str(&text);
+        }
+        if !author.is_empty() {
+            let (text, _) = wrap_vertical_text_svg(author, 150, 1100, 1520, 32, 24, 1);
+            svg.push_str(&text);
+        }
+    }
+
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn text_line_svg(text: &str, x: i32, y: i32, font_size: u32) -> String {
+    format!(
+        "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"serif\" fill=\"#000000\" text-anchor=\"middle\">{}</text>\n",
+        x, y, font_size, text,
+    )
+}
+
+/// The smallest we'll shrink a cover's font before giving up and
+/// truncating, for `wrap_vertical_text_svg`/`wrap_horizontal_text_svg`.
+const COVER_MIN_FONT_SIZE: u32 = 20;
+
+/// Lays `text` out as vertical (top-to-bottom) columns running
+/// right-to-left from `x_start`, wrapping into up to `max_columns`
+/// columns that each span `y_start..y_end`, shrinking the font if the
+/// text doesn't fit even with `max_columns`, and truncating with `…` as
+/// a last resort. Returns the rendered `<text>` elements and the x
+/// coordinate just past the last column used, so the caller can place
+/// the next field without overlapping it.
+fn wrap_vertical_text_svg(
+    text: &str,
+    x_start: i32,
+    y_start: i32,
+    y_end: i32,
+    font_size: u32,
+    column_gap: i32,
+    max_columns: usize,
+) -> (String, i32) {
+    let mut chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return (String::new(), x_start);
+    }
+
+    let mut font_size = font_size;
+    let mut y_step = font_size as i32 + (font_size as i32 / 6);
+    let mut rows_per_column = (((y_end - y_start) / y_step).max(1)) as usize;
+
+    while chars.len() > rows_per_column * max_columns && font_size > COVER_MIN_FONT_SIZE {
+        font_size -= 4;
+        y_step = font_size as i32 + (font_size as i32 / 6);
+        rows_per_column = (((y_end - y_start) / y_step).max(1)) as usize;
+    }
+
+    let capacity = rows_per_column * max_columns;
+    if chars.len() > capacity {
+        chars.truncate(capacity.saturating_sub(1));
+        chars.push('…');
+    }
+
+    let mut svg = String::new();
+    let mut x = x_start;
+    for column_chars in chars.chunks(rows_per_column) {
+        for (row, c) in column_chars.iter().enumerate() {
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"serif\" fill=\"#000000\" text-anchor=\"middle\">{}</text>\n",
+                x,
+                y_start + (row as i32 * y_step),
+                font_size,
+                c,
+            ));
+        }
+        x -= font_size as i32 + column_gap;
+    }
+
+    (svg, x)
+}
+
+/// Lays `text` out as horizontal (left-to-right) lines running
+/// top-to-bottom from `y_start`, wrapping into up to `max_lines` lines
+/// of `max_width` each, shrinking the font if the text doesn't fit even
+/// with `max_lines`, and truncating with `…` as a last resort. Returns
+/// the rendered `<text>` elements and the y coordinate just past the
+/// last line used, so the caller can place the next field without
+/// overlapping it.
+fn wrap_horizontal_text_svg(
+    text: &str,
+    x_center: i32,
+    y_start: i32,
+    y_end: i32,
+    font_size: u32,
+    line_gap: i32,
+    max_lines: usize,
+    max_width: i32,
+) -> (String, i32) {
+    let mut chars: Vec<char> = text.chars().collect();
+    if chars.is_empty() {
+        return (String::new(), y_start);
+    }
+
+    let mut font_size = font_size;
+    let mut line_step = font_size as i32 + line_gap;
+    let mut chars_per_line = ((max_width / font_size as i32).max(1)) as usize;
+    let max_lines_by_height = (((y_end - y_start) / line_step).max(1)) as usize;
+    let max_lines = max_lines.min(max_lines_by_height);
+
+    while chars.len() > chars_per_line * max_lines && font_size > COVER_MIN_FONT_SIZE {
+        font_size -= 4;
+        line_step = font_size as i32 + line_gap;
+        chars_per_line = ((max_width / font_size as i32).max(1)) as usize;
+    }
+
+    let capacity = chars_per_line * max_lines;
+    if chars.len() > capacity {
+        chars.truncate(capacity.saturating_sub(1));
+        chars.push('…');
+    }
+
+    let mut svg = String::new();
+    let mut y = y_start;
+    for line_chars in chars.chunks(chars_per_line) {
+        let line: String = line_chars.iter().collect();
+        svg.push_str(&text_line_svg(&line, x_center, y, font_size));
+        y += line_step;
+    }
+
+    (svg, y)
+}
+
+fn cover_xhtml_page(image_filename: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="ja" xml:lang="ja">
+<head>
+  <meta charset="utf-8" />
+  <title>Cover</title>
+</head>
+<body epub:type="frontmatter">
+<section epub:type="cover" class="cover">
+<img src="{}" alt="cover" />
+</section>
+</body>
+</html>
+"#,
+        image_filename,
+    )
+}
+
 fn epub_content_page(title: &str, content: &str) -> String {
     let mut page = String::new();
 
@@ -176,6 +528,102 @@ fn epub_content_page(title: &str, content: &str) -> String {
     page
 }
 
+/// Converts a chapter's `content_xhtml` fragment (a heading plus `<p>`
+/// paragraphs, `<p class="blank">` spacers, and `<hr/>` separators) to
+/// Markdown.
+fn xhtml_fragment_to_markdown(fragment: &str) -> String {
+    // Matched over the whole fragment rather than split `.lines()` first:
+    // a captured `<p>...</p>` body can itself contain embedded newlines
+    // (see `re_paragraph` in `generate_chapter()`), which would otherwise break it
+    // across several unmatched lines and silently drop it.
+    let re_element = regex::Regex::new(
+        r#"(?s)<h([1-6])>(.*?)</h[1-6]>|<p class="blank"></p>|<hr\s*/?>|<p>(.*?)</p>"#,
+    )
+    .unwrap();
+
+    let mut md = String::new();
+    for c in re_element.captures_iter(fragment) {
+        if let Some(level) = c.get(1) {
+            md.push_str(&"#".repeat(level.as_str().parse().unwrap()));
+            md.push(' ');
+            md.push_str(&c[2]);
+            md.push_str("\n\n");
+        } else if let Some(p) = c.get(3) {
+            md.push_str(p.as_str());
+            md.push_str("\n\n");
+        } else if c[0].starts_with("<hr") {
+            md.push_str("---\n\n");
+        } else {
+            md.push('\n');
+        }
+    }
+
+    md
+}
+
+/// Renders a volume as a single Markdown document, one heading per
+/// chapter.
+fn volume_to_markdown(volume: &Volume) -> String {
+    let mut md = String::new();
+
+    md.push_str(&format!("# {}\n\n", volume.title));
+    let subtitle = composite_subtitle(volume);
+    if !subtitle.is_empty() {
+        md.push_str(&format!("## {}\n\n", subtitle));
+    }
+    md.push_str(&format!("{}\n\n", volume.author));
+
+    for chapter in &volume.chapters {
+        md.push_str(&xhtml_fragment_to_markdown(&chapter.content_xhtml));
+    }
+
+    md
+}
+
+/// Renders a volume as a single self-contained xhtml file, with every
+/// chapter concatenated in sequence and the usual stylesheet inlined.
+fn volume_to_single_html(volume: &Volume, horizontal_text: bool) -> String {
+    let css = format!(
+        "@charset \"utf-8\";\n{}{}",
+        if horizontal_text {
+            CSS_BODY_HORIZONTAL_TEXT
+        } else {
+            CSS_BODY_VERTICAL_TEXT
+        },
+        CSS_MAIN
+    );
+
+    let subtitle = composite_subtitle(volume);
+
+    let mut page = String::new();
+    page.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" lang="ja" xml:lang="ja">
+<head>
+  <meta charset="utf-8" />
+"#);
+    page.push_str(&format!("<title>{}</title>\n", volume.title));
+    page.push_str("<style type=\"text/css\">\n");
+    page.push_str(&css);
+    page.push_str("\n</style>\n</head>\n<body>\n<section class=\"titlepage\">\n");
+    page.push_str(&format!("<h1>{}</h1>\n", ascii_to_fullwidth(&volume.title)));
+    if !subtitle.is_empty() {
+        page.push_str(&format!("<h2>{}</h2>\n", ascii_to_fullwidth(&subtitle)));
+    }
+    page.push_str(&format!("<p>{}</p>\n", ascii_to_fullwidth(&volume.author)));
+    page.push_str("</section>\n");
+
+    for chapter in &volume.chapters {
+        page.push_str("<section>\n");
+        page.push_str(&chapter.content_xhtml);
+        page.push_str("\n</section>\n");
+    }
+
+    page.push_str("</body>\n</html>\n");
+
+    page
+}
+
 const CSS_BODY_VERTICAL_TEXT: &str = r#"
 body {
     writing-mode: vertical-rl;
@@ -282,13 +730,61 @@ rt span.pitch_flat {
 }
 "#;
 
-fn get_page(url: &str) -> Result<String, ureq::Error> {
+/// A persistent, content-addressed on-disk cache of downloaded pages, so
+/// interrupted runs can resume without re-downloading everything.
+struct PageCache {
+    dir: Option<std::path::PathBuf>,
+    refresh: bool,
+}
+
+impl PageCache {
+    /// `dir`: where to store cached pages, or `None` to disable caching
+    /// entirely. `refresh`: bypass (but still repopulate) the cache.
+    fn new(dir: Option<std::path::PathBuf>, refresh: bool) -> Self {
+        if let Some(ref dir) = dir {
+            std::fs::create_dir_all(dir).ok();
+        }
+        Self { dir, refresh }
+    }
+
+    fn path_for(&self, url: &str) -> Option<std::path::PathBuf> {
+        use std::hash::{Hash, Hasher};
+
+        let dir = self.dir.as_ref()?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        url.hash(&mut hasher);
+        Some(dir.join(format!("{:016x}.html", hasher.finish())))
+    }
+
+    fn get(&self, url: &str) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+        std::fs::read_to_string(self.path_for(url)?).ok()
+    }
+
+    fn put(&self, url: &str, content: &str) {
+        if let Some(path) = self.path_for(url) {
+            std::fs::write(path, content).ok();
+        }
+    }
+}
+
+/// A small amount of jitter (in milliseconds, up to `max_ms`) to avoid
+/// every retry in a run landing on the exact same backoff schedule.
+fn backoff_jitter_ms(max_ms: u64) -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().subsec_nanos() as u64;
+    nanos % (max_ms + 1)
+}
+
+fn get_page(url: &str, cache: &PageCache) -> Result<String, ureq::Error> {
     const TIMEOUT_SECS: u64 = 60;
+    const MAX_RETRIES: u32 = 5;
 
-    // IP will be banned for a short time if pages are loaded too fast.
-    // The original script had a wait time of 0.1 seconds, which worked
-    // fine.  0.5 is extra conservative, just to be safe.
-    std::thread::sleep(Duration::from_secs_f32(0.5));
+    if let Some(content) = cache.get(url) {
+        return Ok(content);
+    }
 
     let agent: ureq::Agent = ureq::AgentBuilder::new()
       .timeout(Duration::from_secs(TIMEOUT_SECS))
@@ -296,7 +792,54 @@ fn get_page(url: &str) -> Result<String, ureq::Error> {
       .user_agent("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_10_1) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/39.0.2171.95 Safari/537.36")
       .build();
 
-    Ok(agent.get(url).call()?.into_string().unwrap())
+    let mut attempt = 0;
+    loop {
+        // IP will be banned for a short time if pages are loaded too fast.
+        // The original script had a wait time of 0.1 seconds, which worked
+        // fine.  0.5 is extra conservative, just to be safe.
+        std::thread::sleep(Duration::from_secs_f32(0.5));
+
+        match agent.get(url).call() {
+            Ok(response) => {
+                let content = response.into_string().unwrap();
+                cache.put(url, &content);
+                return Ok(content);
+            }
+
+            Err(ureq::Error::Status(code, response)) if attempt < MAX_RETRIES => {
+                let retry_after = response
+                    .header("Retry-After")
+                    .and_then(|h| h.parse::<u64>().ok());
+                if code != 429 && code != 503 && retry_after.is_none() {
+                    return Err(ureq::Error::Status(code, response));
+                }
+
+                let backoff_secs = retry_after.unwrap_or(2u64.pow(attempt));
+                println!(
+                    "    Got HTTP {}, retrying in {}s...",
+                    code, backoff_secs
+                );
+                std::thread::sleep(
+                    Duration::from_secs(backoff_secs) + Duration::from_millis(backoff_jitter_ms(500)),
+                );
+                attempt += 1;
+            }
+
+            Err(ureq::Error::Transport(e)) if attempt < MAX_RETRIES => {
+                let backoff_secs = 2u64.pow(attempt);
+                println!(
+                    "    Transport error ({}), retrying in {}s...",
+                    e, backoff_secs
+                );
+                std::thread::sleep(
+                    Duration::from_secs(backoff_secs) + Duration::from_millis(backoff_jitter_ms(500)),
+                );
+                attempt += 1;
+            }
+
+            Err(e) => return Err(e),
+        }
+    }
 }
 
 fn maybe_group<'a>(hit: Option<regex::Captures<'a>>, group_index: usize) -> &'a str {
@@ -353,13 +896,25 @@ fn ascii_to_fullwidth(text: &str) -> String {
 
 // Returns (title, xhtml_page).  Note that the content contains the title as a
 // header item as well.  The separate title is for metadata.
-fn generate_chapter(chapter_html_in: &str, title_tag: &str) -> Chapter {
+//
+// `chapter_number`, if given, is prepended to the title as "第N話" (used for
+// `--number-chapters`). `furigana`, if given, annotates each paragraph via
+// `furigana_gen` (used for `--pitch-accent`).
+fn generate_chapter(
+    chapter_html_in: &str,
+    title_tag: &str,
+    chapter_number: Option<usize>,
+    furigana: Option<&furigana_gen::FuriganaGenerator>,
+) -> Chapter {
     let mut text = String::new();
 
     let re_title = regex::Regex::new(r#"(?ms)<h1 class=\"p-novel__title[^>]*>(.*?)</h1>"#).unwrap();
-    let chapter_title = maybe_group(re_title.captures(chapter_html_in), 1)
+    let mut chapter_title = maybe_group(re_title.captures(chapter_html_in), 1)
         .trim()
         .to_string();
+    if let Some(n) = chapter_number {
+        chapter_title = format!("第{}話　{}", n, chapter_title);
+    }
 
     text.push_str(&format!(
         "<{}>{}</{}>\n\n",
@@ -388,8 +943,12 @@ fn generate_chapter(chapter_html_in: &str, title_tag: &str) -> Chapter {
                 // p.blank, this keeps the spacing not completely crazy.
                 text.push_str("<p class=\"blank\"></p>\n");
             } else if paragraph != "" {
+                let paragraph = common_subs(paragraph);
                 text.push_str("<p>");
-                text.push_str(&common_subs(paragraph));
+                text.push_str(&match furigana {
+                    Some(gen) => gen.add_html_furigana(&paragraph, &[]),
+                    None => paragraph,
+                });
                 text.push_str("</p>\n");
             }
         }
@@ -403,15 +962,33 @@ fn generate_chapter(chapter_html_in: &str, title_tag: &str) -> Chapter {
     Chapter {
         title: chapter_title.clone(),
         xhtml_page: epub_content_page(&chapter_title, &text),
+        content_xhtml: text,
     }
 }
 
+/// Which output format to write. Defaults to `Epub` when no subcommand is
+/// given, so existing invocations keep working unchanged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Epub,
+    Markdown,
+    Html,
+}
+
 #[derive(Clone, Debug)]
 struct Args {
     volume: Option<usize>,
     chapters: Option<String>,
     title: Option<String>,
     horizontal_text: bool,
+    number_chapters: bool,
+    cache_dir: Option<String>,
+    refresh: bool,
+    pitch_accent: bool,
+    cover: Option<String>,
+    no_cover: bool,
+    epub_version: u8,
+    format: OutputFormat,
     book: String,
 }
 
@@ -436,14 +1013,62 @@ impl Args {
         let horizontal_text = long("horizontal")
             .help("Renders the book with horizontal left-to-right text (instead of the default vertical right-to-left).")
             .switch();
+        let number_chapters = long("number-chapters")
+            .help("Prepends sequential chapter numbers (\"第1話\" style) to chapter titles, respecting --chapters if given.")
+            .switch();
+        let cache_dir = long("cache-dir")
+            .help("Directory to persistently cache downloaded pages in. Defaults to the OS cache folder.")
+            .argument::<String>("DIR")
+            .optional();
+        let refresh = long("refresh")
+            .help("Bypasses the page cache, re-downloading everything.")
+            .switch();
+        let pitch_accent = long("pitch-accent")
+            .help("Annotates the text with furigana and pitch-accent markers, for learners. \
+                   Note: the bundled dictionary doesn't carry UniDic's accent field, so this \
+                   currently only adds furigana, with no pitch-accent markers.")
+            .switch();
+        let cover = long("cover")
+            .help("Use the given image file as the epub's cover, instead of a generated one.")
+            .argument::<String>("FILE")
+            .optional();
+        let no_cover = long("no-cover")
+            .help("Don't include a cover image at all.")
+            .switch();
+        let epub_version = long("epub-version")
+            .help("Which EPUB version to write: 2 (NCX-only, for legacy readers) or 3 (nav document, default).")
+            .argument::<u8>("VERSION")
+            .fallback(3);
         let book = positional::<String>("BOOK_URL")
             .help("The full url of book's main page on syosetu.com.");
 
+        let epub = bpaf::pure(OutputFormat::Epub)
+            .to_options()
+            .descr("Write one .epub file per volume (default).")
+            .command("epub");
+        let markdown = bpaf::pure(OutputFormat::Markdown)
+            .to_options()
+            .descr("Write one Markdown (.md) file per volume.")
+            .command("markdown");
+        let html = bpaf::pure(OutputFormat::Html)
+            .to_options()
+            .descr("Write one self-contained xhtml file per volume.")
+            .command("html");
+        let format = construct!([epub, markdown, html]).fallback(OutputFormat::Epub);
+
         construct!(Args {
             volume,
             chapters,
             title,
             horizontal_text,
+            number_chapters,
+            cache_dir,
+            refresh,
+            pitch_accent,
+            cover,
+            no_cover,
+            epub_version,
+            format,
             book
         })
         .to_options()
@@ -467,6 +1092,23 @@ impl Args {
             }
         }
 
+        if self.cover.is_some() && self.no_cover {
+            println!("Error: --cover and --no-cover cannot both be given.");
+            return false;
+        }
+
+        if let Some(ref cover) = self.cover {
+            if let Err(e) = std::fs::metadata(cover) {
+                println!("Error: can't read --cover file \"{}\": {}.", cover, e);
+                return false;
+            }
+        }
+
+        if self.epub_version != 2 && self.epub_version != 3 {
+            println!("Error: --epub-version must be 2 or 3.");
+            return false;
+        }
+
         return true;
     }
 }
@@ -487,6 +1129,25 @@ fn main() {
     let main_url = args.book.trim_end_matches("/");
     let base_url = main_url.rsplitn(2, "/").nth(1).unwrap();
 
+    let cache_dir = args
+        .cache_dir
+        .clone()
+        .map(std::path::PathBuf::from)
+        .or_else(|| dirs::cache_dir().map(|d| d.join("syosetu2ebook")));
+    let cache = PageCache::new(cache_dir, args.refresh);
+
+    let furigana = args
+        .pitch_accent
+        .then(|| furigana_gen::FuriganaGenerator::new().with_pitch_accent(true));
+
+    let cover_image = if args.no_cover {
+        None
+    } else if let Some(ref path) = args.cover {
+        Some(CoverImage::Custom(std::path::PathBuf::from(path)))
+    } else {
+        Some(CoverImage::Generated)
+    };
+
     // Download main page (possibly paginated across multiple actual pages).
     println!("Downloading table of contents...");
     let main_page = {
@@ -500,7 +1161,7 @@ fn main() {
         let mut page_num = 1;
         while let Some(url) = next_url {
             println!("    Page {}...", page_num);
-            let page = get_page(&url).unwrap();
+            let page = get_page(&url, &cache).unwrap();
             content.push_str(&page);
 
             let link = maybe_group(re_main_next.captures(&page), 1);
@@ -571,6 +1232,34 @@ fn main() {
         table_of_contents
     };
 
+    let synopsis = {
+        let re = regex::Regex::new(r#"(?ms)<div class=\"p-novel__summary\">(.*?)</div>"#).unwrap();
+        common_subs(maybe_group(re.captures(&main_page), 1).trim())
+    };
+
+    let tags: Vec<String> = {
+        let re_block =
+            regex::Regex::new(r#"(?ms)<div class=\"p-novel__keyword\">(.*?)</div>"#).unwrap();
+        let re_tag = regex::Regex::new(r#"(?ms)<a[^>]*>(.*?)</a>"#).unwrap();
+
+        let block = maybe_group(re_block.captures(&main_page), 1);
+        re_tag
+            .captures_iter(block)
+            .map(|c| common_subs(maybe_group(Some(c), 1).trim()))
+            .filter(|t| !t.is_empty())
+            .collect()
+    };
+
+    let update_date = {
+        let re = regex::Regex::new(r#"(?ms)<div class=\"p-novel__update\">.*?datetime=\"([^"]*)\""#)
+            .unwrap();
+        maybe_group(re.captures(&main_page), 1).trim().to_string()
+    };
+
+    // The ncode is the last path component of the book's url, e.g.
+    // `https://ncode.syosetu.com/n1234ab/` -> `n1234ab`.
+    let identifier = format!("urn:ncode:{}", main_url.rsplitn(2, "/").next().unwrap());
+
     println!("\nTitle: {}", title);
     println!("Author: {}", author);
     println!("Volumes: {}", table_of_contents.len());
@@ -629,9 +1318,14 @@ fn main() {
                     let sub_chapter_url_number =
                         maybe_group(re_chapter_number.captures(chapter_link), 1);
                     let sub_chapter_url = format!("{}/{}", main_url, sub_chapter_url_number);
-                    let chapter_html = get_page(&sub_chapter_url).unwrap();
-
-                    chapters.push(generate_chapter(&chapter_html, "h1"));
+                    let chapter_html = get_page(&sub_chapter_url, &cache).unwrap();
+
+                    chapters.push(generate_chapter(
+                        &chapter_html,
+                        "h1",
+                        args.number_chapters.then(|| chap_i + 1),
+                        furigana.as_ref(),
+                    ));
                 }
 
                 Volume {
@@ -640,14 +1334,16 @@ fn main() {
                     author: author.clone(),
                     chapters: chapters.clone(),
                     chapter_range: args.chapters.as_ref().map(|r| parse_number_range(r)),
+                    synopsis: synopsis.clone(),
+                    tags: tags.clone(),
+                    update_date: update_date.clone(),
+                    identifier: identifier.clone(),
                 }
             };
 
-            // Generate the epub.
+            // Generate and write the output file, in whichever format was
+            // requested.
             {
-                let (composite_subtitle, epub_bytes) =
-                    volume_to_epub(&volume, args.horizontal_text);
-
                 // Output filename, sans extension.
                 let book_filename: String = {
                     let mut book_filename = volume.title.clone();
@@ -655,8 +1351,9 @@ fn main() {
                     if !volume.subtitle.is_empty() {
                         book_filename.push_str(&format!(" - {:02}", vol_i + 1));
                     }
-                    if !composite_subtitle.is_empty() {
-                        book_filename.push_str(&format!(" - {}", composite_subtitle));
+                    let subtitle = composite_subtitle(&volume);
+                    if !subtitle.is_empty() {
+                        book_filename.push_str(&format!(" - {}", subtitle));
                     }
 
                     book_filename
@@ -666,12 +1363,33 @@ fn main() {
                         .into()
                 };
 
-                // Make epub.
-                let epub_filepath = format!("{}.epub", book_filename);
-                {
-                    println!("    Writing \"{}\"", epub_filepath);
-                    let mut f = File::create(&epub_filepath).unwrap();
-                    f.write_all(&epub_bytes).unwrap();
+                match args.format {
+                    OutputFormat::Epub => {
+                        let (_, epub_bytes) = volume_to_epub(
+                            &volume,
+                            args.horizontal_text,
+                            cover_image.as_ref(),
+                            args.epub_version,
+                        );
+                        let filepath = format!("{}.epub", book_filename);
+                        println!("    Writing \"{}\"", filepath);
+                        let mut f = File::create(&filepath).unwrap();
+                        f.write_all(&epub_bytes).unwrap();
+                    }
+                    OutputFormat::Markdown => {
+                        let md = volume_to_markdown(&volume);
+                        let filepath = format!("{}.md", book_filename);
+                        println!("    Writing \"{}\"", filepath);
+                        let mut f = File::create(&filepath).unwrap();
+                        f.write_all(md.as_bytes()).unwrap();
+                    }
+                    OutputFormat::Html => {
+                        let html = volume_to_single_html(&volume, args.horizontal_text);
+                        let filepath = format!("{}.xhtml", book_filename);
+                        println!("    Writing \"{}\"", filepath);
+                        let mut f = File::create(&filepath).unwrap();
+                        f.write_all(html.as_bytes()).unwrap();
+                    }
                 }
             }
         }
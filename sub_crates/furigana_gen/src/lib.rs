@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     // fs::File,
     io::{Cursor, Read},
 };
@@ -8,10 +9,197 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 use vibrato::{Dictionary, Tokenizer};
 
+mod pitch;
+mod romaji;
+use romaji::kana_to_romaji;
+
 const DICT: &[u8] = include_bytes!("../dictionary/system.dic.lz4");
 
+// Generated by build.rs from `data/kanji_frequency.txt`.  Defines
+// `const KANJI_FREQ: &[char]`, ordered from most to least frequent.
+include!(concat!(env!("OUT_DIR"), "/kanji_freq_inc.rs"));
+
+/// Maps a kanji to its rank (0 = most frequent) in `KANJI_FREQ`.
+static KANJI_FREQ_RANK: Lazy<HashMap<char, usize>> = Lazy::new(|| {
+    KANJI_FREQ
+        .iter()
+        .enumerate()
+        .map(|(rank, &c)| (c, rank))
+        .collect()
+});
+
+/// Controls what script generated ruby text is rendered in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RubyReadingStyle {
+    Hiragana,
+    Katakana,
+    Romaji,
+}
+
+/// Configures the HTML tags used to mark up generated ruby text.
+///
+/// By default no `<rb>` tag is emitted (the base text is written directly
+/// inside `<ruby>`) and no `<rp>` fallback parentheses are emitted either,
+/// matching `add_html_furigana`'s historical output.
+#[derive(Debug, Clone)]
+pub struct RubyMarkup {
+    /// Whether to wrap the base text in an explicit `<rb>` tag.
+    pub emit_rb: bool,
+
+    /// Fallback parenthesis strings, `(open, close)`, wrapped around the
+    /// reading in `<rp>` tags for readers that don't support `<ruby>`.
+    pub rp: Option<(String, String)>,
+}
+
+impl Default for RubyMarkup {
+    fn default() -> Self {
+        Self {
+            emit_rb: false,
+            rp: None,
+        }
+    }
+}
+
+impl RubyMarkup {
+    /// The common `<rp>(</rp>`/`<rp>)</rp>` fallback, using ASCII
+    /// parentheses.
+    pub fn with_ascii_rp(mut self) -> Self {
+        self.rp = Some(("(".into(), ")".into()));
+        self
+    }
+}
+
+/// A notation for an author-supplied inline furigana annotation, e.g.
+/// `漢字（かんじ）` or `[漢字]{かんじ}`, recognized in the input text and
+/// converted straight to `<ruby>` markup, bypassing the tokenizer for
+/// that span so the author's reading always wins.
+#[derive(Debug, Clone)]
+pub struct InlineFuriganaSyntax {
+    /// Delimiters wrapping the surface form, e.g. `("[", "]")`.  `None`
+    /// means the surface form is undelimited: the run of kanji
+    /// immediately preceding the reading delimiters.
+    pub surface: Option<(String, String)>,
+
+    /// Delimiters wrapping the reading, e.g. `("{", "}")`.
+    pub reading: (String, String),
+}
+
+/// The inline furigana notations recognized by default: `漢字（かんじ）`
+/// (also accepting the halfwidth `(...)` form) and `[漢字]{かんじ}`.
+pub fn default_inline_furigana_syntaxes() -> Vec<InlineFuriganaSyntax> {
+    vec![
+        InlineFuriganaSyntax {
+            surface: None,
+            reading: ("（".into(), "）".into()),
+        },
+        InlineFuriganaSyntax {
+            surface: None,
+            reading: ("(".into(), ")".into()),
+        },
+        InlineFuriganaSyntax {
+            surface: Some(("[".into(), "]".into())),
+            reading: ("{".into(), "}".into()),
+        },
+    ]
+}
+
+/// Compiles an `InlineFuriganaSyntax` into a regex with two capture
+/// groups: the surface form, then the reading.
+fn build_inline_regex(syntax: &InlineFuriganaSyntax) -> Regex {
+    let (read_open, read_close) = &syntax.reading;
+
+    // The reading itself must be kana (plus the chōonpu `ー`): otherwise
+    // ordinary parenthetical asides like `田中（代表）` or `時間（午後三時）`
+    // get misread as furigana annotations.
+    const KANA_CLASS: &str = r"[\p{Hiragana}\p{Katakana}ー]+";
+
+    let pattern = if let Some((surf_open, surf_close)) = &syntax.surface {
+        format!(
+            "{}(.+?){}{}({}){}",
+            regex::escape(surf_open),
+            regex::escape(surf_close),
+            regex::escape(read_open),
+            KANA_CLASS,
+            regex::escape(read_close),
+        )
+    } else {
+        // No surface delimiters: the surface form is the run of kanji
+        // immediately preceding the reading delimiters.
+        format!(
+            r"(\p{{Han}}+){}({}){}",
+            regex::escape(read_open),
+            KANA_CLASS,
+            regex::escape(read_close),
+        )
+    };
+
+    Regex::new(&pattern).unwrap()
+}
+
+/// A span of text split out by `split_inline_furigana`: either plain text
+/// to be run through the tokenizer as usual, or an author-supplied
+/// surface/reading pair to be rendered as ruby directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InlineSegment<'a> {
+    Plain(&'a str),
+    Ruby(&'a str, &'a str),
+}
+
+/// Splits `text` on author-supplied inline furigana annotations matching
+/// any of `syntaxes`, in the order they appear in the text.  When more
+/// than one syntax could match at a given position, the one yielding the
+/// earliest (and, for ties, longest) match wins.
+fn split_inline_furigana<'a>(
+    text: &'a str,
+    syntaxes: &[InlineFuriganaSyntax],
+) -> Vec<InlineSegment<'a>> {
+    if syntaxes.is_empty() {
+        return vec![InlineSegment::Plain(text)];
+    }
+
+    let regexes: Vec<Regex> = syntaxes.iter().map(build_inline_regex).collect();
+
+    let mut segments = Vec::new();
+    let mut pos = 0;
+    while pos < text.len() {
+        let best = regexes
+            .iter()
+            .filter_map(|re| re.captures(&text[pos..]))
+            .min_by_key(|caps| {
+                let m = caps.get(0).unwrap();
+                (m.start(), std::cmp::Reverse(m.end() - m.start()))
+            });
+
+        let Some(caps) = best else {
+            break;
+        };
+
+        let whole = caps.get(0).unwrap();
+        if whole.start() > 0 {
+            segments.push(InlineSegment::Plain(&text[pos..pos + whole.start()]));
+        }
+        segments.push(InlineSegment::Ruby(
+            caps.get(1).unwrap().as_str(),
+            caps.get(2).unwrap().as_str(),
+        ));
+        pos += whole.end();
+    }
+
+    if pos < text.len() {
+        segments.push(InlineSegment::Plain(&text[pos..]));
+    }
+
+    segments
+}
+
 pub struct FuriganaGenerator {
     tokenizer: Tokenizer,
+    overrides: HashMap<String, String>,
+    reading_style: RubyReadingStyle,
+    ruby_markup: RubyMarkup,
+    known_kanji_rank_threshold: usize,
+    inline_furigana_syntaxes: Vec<InlineFuriganaSyntax>,
+    pitch_accent: bool,
 }
 
 impl FuriganaGenerator {
@@ -28,14 +216,119 @@ impl FuriganaGenerator {
         };
         Self {
             tokenizer: Tokenizer::new(dict),
+            overrides: HashMap::new(),
+            reading_style: RubyReadingStyle::Katakana,
+            ruby_markup: RubyMarkup::default(),
+            known_kanji_rank_threshold: 0,
+            inline_furigana_syntaxes: default_inline_furigana_syntaxes(),
+            pitch_accent: false,
         }
     }
 
+    /// Registers a table of explicit surface-form -> reading overrides.
+    ///
+    /// The tokenizer sometimes gets readings wrong for names, rare
+    /// compounds, or gikun, and there's no good general-purpose fix for
+    /// that.  This lets a caller correct specific cases directly.
+    ///
+    /// A key may optionally be qualified with the token's part-of-speech
+    /// (the first comma-separated field of its dictionary feature string)
+    /// by joining the two with a NUL byte, e.g. `"{surface}\0{pos}"`, to
+    /// disambiguate a surface form that's read differently depending on
+    /// how it's used.  Unqualified keys are used as a fallback.
+    pub fn with_overrides(mut self, overrides: HashMap<String, String>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Sets the script used for generated ruby text.  Defaults to
+    /// `RubyReadingStyle::Katakana`, matching the tokenizer's native
+    /// reading feature.
+    pub fn with_reading_style(mut self, reading_style: RubyReadingStyle) -> Self {
+        self.reading_style = reading_style;
+        self
+    }
+
+    /// Sets how the generated `<ruby>` markup is put together, e.g. to add
+    /// `<rp>` fallback parentheses for readers that don't support ruby.
+    pub fn with_ruby_markup(mut self, ruby_markup: RubyMarkup) -> Self {
+        self.ruby_markup = ruby_markup;
+        self
+    }
+
+    /// Treats the `threshold` most frequent kanji (per `KANJI_FREQ`) as
+    /// known, suppressing furigana on them, in addition to whatever is
+    /// passed explicitly to `add_html_furigana`'s `known` list.  Defaults
+    /// to `0`, i.e. no kanji are treated as known this way.
+    pub fn with_known_kanji_rank_threshold(mut self, threshold: usize) -> Self {
+        self.known_kanji_rank_threshold = threshold;
+        self
+    }
+
+    /// Sets the notations recognized as author-supplied inline furigana
+    /// annotations in the input (see `InlineFuriganaSyntax`).  Defaults to
+    /// `default_inline_furigana_syntaxes()`; pass an empty `Vec` to
+    /// disable this and always tokenize.
+    pub fn with_inline_furigana_syntaxes(mut self, syntaxes: Vec<InlineFuriganaSyntax>) -> Self {
+        self.inline_furigana_syntaxes = syntaxes;
+        self
+    }
+
+    /// Annotates `<ruby>`/`<rt>` furigana (or, for tokens that don't need
+    /// furigana, the surface form itself) with pitch-accent mora spans,
+    /// per UniDic's `aType` accent-drop position.  Requires the
+    /// tokenizer's dictionary to carry that as a third comma-separated
+    /// feature field; without it, tokens render unaccented. Defaults to
+    /// `false`.
+    pub fn with_pitch_accent(mut self, pitch_accent: bool) -> Self {
+        self.pitch_accent = pitch_accent;
+        self
+    }
+
     pub fn add_html_furigana(&self, text: &str, known: &[char]) -> String {
-        add_html_furigana_skip_already_ruby(&text, &self.tokenizer, known)
+        add_html_furigana_skip_already_ruby(
+            &text,
+            &self.tokenizer,
+            known,
+            self.known_kanji_rank_threshold,
+            &self.overrides,
+            self.reading_style,
+            &self.ruby_markup,
+            &self.inline_furigana_syntaxes,
+            self.pitch_accent,
+        )
     }
 }
 
+/// Renders a tokenizer/override reading (native katakana) in the
+/// requested script.
+fn render_reading(furi: &str, style: RubyReadingStyle) -> String {
+    match style {
+        RubyReadingStyle::Katakana => furi
+            .chars()
+            .map(|c| hiragana_to_katakana(c).unwrap_or(c))
+            .collect(),
+        RubyReadingStyle::Hiragana => furi
+            .chars()
+            .map(|c| katakana_to_hiragana(c).unwrap_or(c))
+            .collect(),
+        RubyReadingStyle::Romaji => kana_to_romaji(furi),
+    }
+}
+
+/// Looks up an override reading for a token, preferring a key qualified by
+/// the token's part-of-speech over the plain surface form.
+fn lookup_override<'a>(
+    overrides: &'a HashMap<String, String>,
+    surface: &str,
+    pos: &str,
+) -> Option<&'a str> {
+    overrides
+        .get(&format!("{}\0{}", surface, pos))
+        .or_else(|| overrides.get(surface))
+        .map(|s| s.as_str())
+}
+
 fn to_str<B: std::ops::Deref<Target = [u8]>>(bytes: &B) -> &str {
     std::str::from_utf8(&bytes.deref()).unwrap()
 }
@@ -45,6 +338,12 @@ fn add_html_furigana_skip_already_ruby(
     text: &str,
     tokenizer: &Tokenizer,
     known: &[char],
+    known_kanji_rank_threshold: usize,
+    overrides: &HashMap<String, String>,
+    style: RubyReadingStyle,
+    markup: &RubyMarkup,
+    inline_syntaxes: &[InlineFuriganaSyntax],
+    pitch_accent: bool,
 ) -> String {
     use quick_xml::{events::Event, Reader};
 
@@ -76,7 +375,25 @@ fn add_html_furigana_skip_already_ruby(
 
             Ok(Event::Text(e)) => {
                 if rubys <= 0 {
-                    new_text.push_str(&add_html_furigana(to_str(&e), tokenizer, known));
+                    for segment in split_inline_furigana(to_str(&e), inline_syntaxes) {
+                        match segment {
+                            InlineSegment::Plain(s) => {
+                                new_text.push_str(&add_html_furigana(
+                                    s,
+                                    tokenizer,
+                                    known,
+                                    known_kanji_rank_threshold,
+                                    overrides,
+                                    style,
+                                    markup,
+                                    pitch_accent,
+                                ));
+                            }
+                            InlineSegment::Ruby(surf, furi) => {
+                                push_ruby(&mut new_text, surf, furi, style, markup);
+                            }
+                        }
+                    }
                 } else {
                     write_xml(&mut new_text, &Event::Text(e));
                 }
@@ -155,7 +472,16 @@ fn write_xml(text: &mut String, event: &quick_xml::events::Event) {
 }
 
 /// Adds furigana to Japanese text, using html ruby tags.
-fn add_html_furigana(text: &str, tokenizer: &Tokenizer, known: &[char]) -> String {
+fn add_html_furigana(
+    text: &str,
+    tokenizer: &Tokenizer,
+    known: &[char],
+    known_kanji_rank_threshold: usize,
+    overrides: &HashMap<String, String>,
+    style: RubyReadingStyle,
+    markup: &RubyMarkup,
+    pitch_accent: bool,
+) -> String {
     let mut worker = tokenizer.new_worker();
 
     worker.reset_sentence(text);
@@ -165,35 +491,112 @@ fn add_html_furigana(text: &str, tokenizer: &Tokenizer, known: &[char]) -> Strin
     for i in 0..worker.num_tokens() {
         let t = worker.token(i);
         let surface = t.surface();
-        let kana = t.feature().split(",").nth(1).unwrap();
+        let pos = t.feature().split(",").next().unwrap_or("");
+        let kana = lookup_override(overrides, surface, pos)
+            .unwrap_or_else(|| t.feature().split(",").nth(1).unwrap());
+
+        if pitch_accent {
+            push_pitch_accented(
+                &mut new_text,
+                surface,
+                kana,
+                t.feature(),
+                known,
+                known_kanji_rank_threshold,
+                style,
+                markup,
+            );
+            continue;
+        }
 
-        let furigana_text = apply_furigana(surface, kana, known);
+        let furigana_text = apply_furigana(surface, kana, known, known_kanji_rank_threshold);
 
         for (surf, furi) in furigana_text.iter() {
-            if furi.is_empty() {
-                new_text.push_str(surf);
-                continue;
-            }
-
-            new_text.push_str("<ruby>");
-            new_text.push_str(surf);
-            new_text.push_str("<rt>");
-            new_text.push_str(furi);
-            new_text.push_str("</rt></ruby>");
+            push_ruby(&mut new_text, surf, furi, style, markup);
         }
     }
 
     new_text
 }
 
+/// Renders a token with pitch-accent mora spans (see `pitch` module),
+/// nested inside `<rt>` furigana where present, or directly around the
+/// surface form for tokens that don't need furigana — matching the two
+/// `span.pitch_accent`/`rt span.pitch_accent` CSS rules.
+///
+/// Accent position is counted across the token's whole reading, so unlike
+/// the normal path this doesn't split ruby per-kanji via `apply_furigana`
+/// (that would require re-deriving which morae fall in which sub-span).
+fn push_pitch_accented(
+    text: &mut String,
+    surface: &str,
+    kana: &str,
+    feature: &str,
+    known: &[char],
+    known_kanji_rank_threshold: usize,
+    style: RubyReadingStyle,
+    markup: &RubyMarkup,
+) {
+    // UniDic's accent-drop position, if the tokenizer's dictionary
+    // carries it as a third feature field.
+    let a_type = feature.split(",").nth(2).and_then(|s| s.parse::<usize>().ok());
+
+    if furigana_unneeded(surface, known, known_kanji_rank_threshold) {
+        let morae = pitch::split_morae(surface);
+        text.push_str(&pitch::wrap_pitch_accent(&morae, a_type));
+    } else {
+        let morae = pitch::split_morae(kana);
+        let accented_reading = pitch::wrap_pitch_accent(&morae, a_type);
+        push_ruby(text, surface, &accented_reading, style, markup);
+    }
+}
+
+/// Appends `surf` to `text`, wrapped in `<ruby>` markup against `furi` (per
+/// `style` and `markup`) unless `furi` is empty, in which case `surf` is
+/// appended verbatim.
+fn push_ruby(text: &mut String, surf: &str, furi: &str, style: RubyReadingStyle, markup: &RubyMarkup) {
+    if furi.is_empty() {
+        text.push_str(surf);
+        return;
+    }
+
+    text.push_str("<ruby>");
+    if markup.emit_rb {
+        text.push_str("<rb>");
+        text.push_str(surf);
+        text.push_str("</rb>");
+    } else {
+        text.push_str(surf);
+    }
+    if let Some((open, _)) = &markup.rp {
+        text.push_str("<rp>");
+        text.push_str(open);
+        text.push_str("</rp>");
+    }
+    text.push_str("<rt>");
+    text.push_str(&render_reading(furi, style));
+    text.push_str("</rt>");
+    if let Some((_, close)) = &markup.rp {
+        text.push_str("<rp>");
+        text.push_str(close);
+        text.push_str("</rp>");
+    }
+    text.push_str("</ruby>");
+}
+
 /// Returns a segmented list of (surface, furigana) pairs.
 ///
 /// The furigana component of a pair may be empty, indicating no
 /// furigana is needed for that surface element.
-fn apply_furigana<'a>(surface: &'a str, kana: &'a str, known: &[char]) -> Vec<(&'a str, &'a str)> {
+fn apply_furigana<'a>(
+    surface: &'a str,
+    kana: &'a str,
+    known: &[char],
+    known_kanji_rank_threshold: usize,
+) -> Vec<(&'a str, &'a str)> {
     let mut out = Vec::new();
 
-    if furigana_unneeded(surface, known) {
+    if furigana_unneeded(surface, known, known_kanji_rank_threshold) {
         out.push((surface, ""));
         return out;
     }
@@ -235,36 +638,150 @@ fn apply_furigana<'a>(surface: &'a str, kana: &'a str, known: &[char]) -> Vec<(&
         kana = &kana[..end_k];
     }
 
-    // Try to uniquely match kana in the middle.
-    //
-    // This is just best-effort, and bails in any non-trivial cases.
-    while let Some((si, sc)) = surface.char_indices().find(|(_, c)| is_kana(*c)) {
-        // If there's more than one match, bail.
-        let equivalent_kana_count = kana
-            .chars()
-            .map(|c| is_equivalent_kana(c, sc))
-            .fold(0usize, |count, hit| count + hit as usize);
-        if equivalent_kana_count != 1 {
-            break;
-        }
+    // Align whatever's left in the middle.
+    let end_piece = out.pop().unwrap();
+    out.extend(align_middle(surface, kana));
+    out.push(end_piece);
 
-        // Find the one match.
-        let (ki, kc) = kana
-            .char_indices()
-            .find(|(_, c)| is_equivalent_kana(sc, *c))
-            .unwrap();
+    out.iter().filter(|(s, _)| !s.is_empty()).copied().collect()
+}
+
+/// A single step in a DP alignment path: either a kana character matching
+/// itself in the reading (at no cost), or a non-kana character consuming
+/// some number of reading characters (at a cost, see `align_middle`).
+#[derive(Debug, Clone, Copy)]
+enum AlignStep {
+    KanaAnchor,
+    Reading(usize),
+}
 
-        // Insert the segments.
-        out.insert(out.len() - 2, (&surface[..si], &kana[..ki]));
-        out.insert(out.len() - 2, (&surface[si..(si + sc.len_utf8())], ""));
-        surface = &surface[(si + sc.len_utf8())..];
-        kana = &kana[(ki + kc.len_utf8())..];
+/// Sets `*cell` to `(cost, step)` if that's cheaper than (or as cheap as)
+/// what's already there.
+fn relax(cell: &mut Option<(u32, AlignStep)>, cost: u32, step: AlignStep) {
+    if cell.map(|(c, _)| cost <= c).unwrap_or(true) {
+        *cell = Some((cost, step));
     }
+}
 
-    // Left over.
-    out.insert(out.len() - 2, (surface, kana));
+/// Aligns a surface/reading pair that has no leading or trailing kana in
+/// common, via dynamic programming, and returns it as a list of
+/// (surface, furigana) spans whose surfaces concatenate back to `surface`
+/// exactly.  Kana that matches itself in the reading comes back as its
+/// own span with an empty furigana component; runs of non-kana characters
+/// with no such anchor in between are coalesced into a single span
+/// against whatever reading spans them.
+fn align_middle<'a>(surface: &'a str, kana: &'a str) -> Vec<(&'a str, &'a str)> {
+    // Fast path: nothing to align against, so the whole thing is one span.
+    if !surface.chars().any(is_kana) {
+        return vec![(surface, kana)];
+    }
 
-    out.iter().filter(|(s, _)| !s.is_empty()).copied().collect()
+    // The maximum number of reading characters attributed to a single
+    // non-kana (typically kanji) character.
+    const MAX_READING_LEN: usize = 4;
+
+    let surf_chars: Vec<char> = surface.chars().collect();
+    let read_chars: Vec<char> = kana.chars().collect();
+    let m = surf_chars.len();
+    let n = read_chars.len();
+
+    // dp[i][j]: cheapest way to have consumed the first `i` surface chars
+    // and the first `j` reading chars, and the step that got us there.
+    let mut dp: Vec<Vec<Option<(u32, AlignStep)>>> = vec![vec![None; n + 1]; m + 1];
+    dp[0][0] = Some((0, AlignStep::KanaAnchor)); // Step is unused for the origin.
+
+    for i in 0..m {
+        for j in 0..=n {
+            let Some((cost, _)) = dp[i][j] else {
+                continue;
+            };
+
+            if is_kana(surf_chars[i]) {
+                // A kana character is a hard anchor: it must match the
+                // reading at its current position, or this path is a
+                // dead end.
+                if j < n && is_equivalent_kana(surf_chars[i], read_chars[j]) {
+                    relax(&mut dp[i + 1][j + 1], cost, AlignStep::KanaAnchor);
+                }
+                continue;
+            }
+
+            // A non-kana character may consume 1..=K reading characters.
+            // The quadratic cost means that, for a fixed total reading
+            // length, the solver prefers spreading it as evenly as
+            // possible among the characters that consume it.
+            for k in 1..=MAX_READING_LEN.min(n - j) {
+                relax(
+                    &mut dp[i + 1][j + k],
+                    cost + (k * k) as u32,
+                    AlignStep::Reading(k),
+                );
+            }
+        }
+    }
+
+    // This shouldn't normally happen (every kana in the surface should be
+    // able to anchor to itself in the reading somewhere), but fall back
+    // to a single span rather than panicking if it does.
+    if dp[m][n].is_none() {
+        return vec![(surface, kana)];
+    }
+
+    // Walk the chosen path back from (m, n) to (0, 0), then reverse it.
+    let mut steps = Vec::with_capacity(m);
+    let (mut i, mut j) = (m, n);
+    while i > 0 {
+        let (_, step) = dp[i][j].unwrap();
+        match step {
+            AlignStep::KanaAnchor => {
+                steps.push((i - 1, j - 1, step));
+                i -= 1;
+                j -= 1;
+            }
+            AlignStep::Reading(k) => {
+                steps.push((i - 1, j - k, step));
+                i -= 1;
+                j -= k;
+            }
+        }
+    }
+    steps.reverse();
+
+    // Byte offsets of each character boundary, so spans can be sliced
+    // back out of the original strings by character index.
+    let mut surf_offsets: Vec<usize> = surface.char_indices().map(|(b, _)| b).collect();
+    surf_offsets.push(surface.len());
+    let mut read_offsets: Vec<usize> = kana.char_indices().map(|(b, _)| b).collect();
+    read_offsets.push(kana.len());
+
+    let mut result: Vec<(&str, &str)> = Vec::new();
+    let mut run_start: Option<(usize, usize)> = None;
+    for (i, j, step) in steps {
+        match step {
+            AlignStep::KanaAnchor => {
+                if let Some((si, sj)) = run_start.take() {
+                    result.push((
+                        &surface[surf_offsets[si]..surf_offsets[i]],
+                        &kana[read_offsets[sj]..read_offsets[j]],
+                    ));
+                }
+                result.push((&surface[surf_offsets[i]..surf_offsets[i + 1]], ""));
+            }
+            AlignStep::Reading(_) => {
+                if run_start.is_none() {
+                    run_start = Some((i, j));
+                }
+            }
+        }
+    }
+    if let Some((si, sj)) = run_start.take() {
+        result.push((
+            &surface[surf_offsets[si]..surf_offsets[m]],
+            &kana[read_offsets[sj]..read_offsets[n]],
+        ));
+    }
+
+    result
 }
 
 /// Due to the way this is used, this isn't meant to be exact, but instead
@@ -330,9 +847,22 @@ pub fn normalize_kana(c: char) -> Option<char> {
 }
 
 /// Returns true if furigana defininitely isn't needed.
-pub fn furigana_unneeded(text: &str, known: &[char]) -> bool {
-    text.chars()
-        .all(|c| is_kana(c) || c.is_ascii() || c.is_numeric() || known.contains(&c))
+///
+/// `known_kanji_rank_threshold` treats the N most frequent kanji (per
+/// `KANJI_FREQ`) as known, in addition to anything listed explicitly in
+/// `known`, so callers can say "no furigana on the top 500 kanji" without
+/// enumerating them.  Pass `0` to disable this and rely solely on `known`.
+pub fn furigana_unneeded(text: &str, known: &[char], known_kanji_rank_threshold: usize) -> bool {
+    text.chars().all(|c| {
+        is_kana(c)
+            || c.is_ascii()
+            || c.is_numeric()
+            || known.contains(&c)
+            || KANJI_FREQ_RANK
+                .get(&c)
+                .map(|&rank| rank < known_kanji_rank_threshold)
+                .unwrap_or(false)
+    })
 }
 
 pub fn hiragana_to_katakana(c: char) -> Option<char> {
@@ -361,7 +891,7 @@ mod tests {
     fn apply_furigana_01() {
         let surface = "へぇ";
         let kana = "ヘー";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
         assert_eq!(&[("へぇ", "")], &pairs[..]);
     }
@@ -370,7 +900,7 @@ mod tests {
     fn apply_furigana_02() {
         let surface = "へぇー";
         let kana = "ヘー";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
         assert_eq!(&[("へぇー", "")], &pairs[..]);
     }
@@ -379,7 +909,7 @@ mod tests {
     fn apply_furigana_03() {
         let surface = "へ";
         let kana = "え";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
         assert_eq!(&[("へ", "")], &pairs[..]);
     }
@@ -388,7 +918,7 @@ mod tests {
     fn apply_furigana_04() {
         let surface = "食べる";
         let kana = "タベル";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
         assert_eq!(&[("食", "タ"), ("べる", "")], &pairs[..]);
     }
@@ -397,7 +927,7 @@ mod tests {
     fn apply_furigana_05() {
         let surface = "流れ出す";
         let kana = "ながれだす";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
         assert_eq!(
             &[("流", "なが"), ("れ", ""), ("出", "だ"), ("す", "")],
@@ -407,11 +937,25 @@ mod tests {
 
     #[test]
     fn apply_furigana_06() {
+        // Previously this bailed to whole-word ruby because of the
+        // repeated の in the reading; the DP alignment now resolves it.
+        let surface = "物の怪";
+        let kana = "もののけ";
+        let pairs = apply_furigana(surface, kana, &[], 0);
+
+        assert_eq!(&[("物", "もの"), ("の", ""), ("怪", "け")], &pairs[..]);
+    }
+
+    #[test]
+    fn apply_furigana_07() {
+        // Concatenating the surfaces must always reproduce the input
+        // surface exactly, even for an alignment this ambiguous.
         let surface = "物の怪";
         let kana = "もののけ";
-        let pairs = apply_furigana(surface, kana, &[]);
+        let pairs = apply_furigana(surface, kana, &[], 0);
 
-        assert_eq!(&[("物の怪", "もののけ")], &pairs[..]);
+        let rejoined: String = pairs.iter().map(|(s, _)| *s).collect();
+        assert_eq!(surface, rejoined);
     }
 
     #[test]
@@ -467,4 +1011,96 @@ mod tests {
             r#"<sup class="食う"><ruby>食<rt>タ</rt></ruby>べる</sup>のは<ruby>良</ruby>いね！<hi />"#
         );
     }
+
+    #[test]
+    fn add_html_furigana_inline_01() {
+        // Author-supplied readings bypass the tokenizer entirely, and win
+        // even over whatever the tokenizer would have guessed.
+        let gen = FuriganaGenerator::new();
+
+        let text = gen.add_html_furigana("親父（おやじ）と[従者]{ともがら}", &[]);
+
+        assert_eq!(
+            text,
+            "<ruby>親父<rt>オヤジ</rt></ruby>と<ruby>従者<rt>トモガラ</rt></ruby>"
+        );
+    }
+
+    #[test]
+    fn split_inline_furigana_non_kana_reading_untouched() {
+        // Ordinary parenthetical asides (ages, titles, stage directions)
+        // aren't kana, and must not be mistaken for furigana annotations.
+        let text = "田中（代表）と話した。";
+        let segments = split_inline_furigana(text, &default_inline_furigana_syntaxes());
+
+        assert_eq!(1, segments.len());
+        assert!(matches!(segments[0], InlineSegment::Plain(s) if s == text));
+    }
+
+    #[test]
+    fn lookup_override_01() {
+        let mut overrides = HashMap::new();
+        overrides.insert("主人公".to_string(), "ぬし".to_string());
+
+        assert_eq!(
+            Some("ぬし"),
+            lookup_override(&overrides, "主人公", "名詞-普通名詞-一般")
+        );
+        assert_eq!(None, lookup_override(&overrides, "従者", "名詞-普通名詞-一般"));
+    }
+
+    #[test]
+    fn lookup_override_02() {
+        // A part-of-speech-qualified key takes precedence over an
+        // unqualified one for the same surface form.
+        let mut overrides = HashMap::new();
+        overrides.insert("大人".to_string(), "おとな".to_string());
+        overrides.insert("大人\0名詞-固有名詞-一般".to_string(), "タイジン".to_string());
+
+        assert_eq!(
+            Some("タイジン"),
+            lookup_override(&overrides, "大人", "名詞-固有名詞-一般")
+        );
+        assert_eq!(
+            Some("おとな"),
+            lookup_override(&overrides, "大人", "名詞-普通名詞-一般")
+        );
+    }
+
+    #[test]
+    fn render_reading_01() {
+        assert_eq!("タベ", render_reading("タベ", RubyReadingStyle::Katakana));
+        assert_eq!("たべ", render_reading("タベ", RubyReadingStyle::Hiragana));
+        assert_eq!("tabe", render_reading("タベ", RubyReadingStyle::Romaji));
+    }
+
+    #[test]
+    fn add_html_furigana_rp_01() {
+        let gen = FuriganaGenerator::new().with_ruby_markup(RubyMarkup::default().with_ascii_rp());
+
+        let text = gen.add_html_furigana("食べる", &[]);
+
+        assert_eq!(text, "<ruby>食<rp>(</rp><rt>タ</rt><rp>)</rp></ruby>べる");
+    }
+
+    #[test]
+    fn add_html_furigana_pitch_accent_01() {
+        // This dictionary doesn't carry UniDic's aType feature field, so
+        // pitch-accent mode currently falls back to plain, unaccented
+        // furigana over the whole token (rather than per-kanji ruby).
+        let gen = FuriganaGenerator::new().with_pitch_accent(true);
+
+        let text = gen.add_html_furigana("食べる", &[]);
+
+        assert_eq!(text, "<ruby>食べ<rt>タベ</rt></ruby>る");
+    }
+
+    #[test]
+    fn furigana_unneeded_rank_threshold_01() {
+        // The most frequent kanji, rank 0, is known with any threshold >= 1.
+        let most_frequent = KANJI_FREQ[0];
+
+        assert!(!furigana_unneeded(&most_frequent.to_string(), &[], 0));
+        assert!(furigana_unneeded(&most_frequent.to_string(), &[], 1));
+    }
 }
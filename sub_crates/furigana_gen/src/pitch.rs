@@ -0,0 +1,97 @@
+//! Pitch-accent mora splitting and span-wrapping, driven by UniDic's
+//! accent-drop position (`aType`).
+
+/// Splits a kana reading (or surface kana run) into morae: a base kana
+/// plus any immediately following small kana (ゃゅょぁぃぅぇぉ, and their
+/// katakana equivalents) or chōonpu (ー) counts as one mora.
+pub fn split_morae(text: &str) -> Vec<&str> {
+    let mut morae: Vec<(usize, usize)> = Vec::new();
+
+    for (i, c) in text.char_indices() {
+        if is_mora_continuation(c) && !morae.is_empty() {
+            morae.last_mut().unwrap().1 = i + c.len_utf8();
+        } else {
+            morae.push((i, i + c.len_utf8()));
+        }
+    }
+
+    morae.into_iter().map(|(s, e)| &text[s..e]).collect()
+}
+
+fn is_mora_continuation(c: char) -> bool {
+    matches!(
+        c,
+        'ゃ' | 'ゅ' | 'ょ' | 'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'ャ' | 'ュ' | 'ョ' | 'ァ' | 'ィ'
+            | 'ゥ' | 'ェ' | 'ォ' | 'ー'
+    )
+}
+
+/// Wraps `morae` in `<span class="pitch_accent">`/`<span class="pitch_flat">`
+/// runs per UniDic's `aType` accent-drop position, `n`: for an accented
+/// word (`n >= 1`) morae `1..n` (1-indexed) get `pitch_accent` and the rest
+/// get `pitch_flat`; for heiban (`n == 0`) mora 1 gets `pitch_flat` and the
+/// rest get `pitch_accent`. Compound/particle boundaries are the caller's
+/// responsibility (call this once per token).
+///
+/// Returns `morae` concatenated verbatim, with no spans, if `a_type` is
+/// `None` — e.g. the tokenizer's dictionary doesn't carry an accent field.
+pub fn wrap_pitch_accent(morae: &[&str], a_type: Option<usize>) -> String {
+    let Some(n) = a_type else {
+        return morae.concat();
+    };
+
+    let mut out = String::new();
+    for (i, mora) in morae.iter().enumerate() {
+        let accented = if n == 0 { i >= 1 } else { i < n };
+        let class = if accented { "pitch_accent" } else { "pitch_flat" };
+        out.push_str("<span class=\"");
+        out.push_str(class);
+        out.push_str("\">");
+        out.push_str(mora);
+        out.push_str("</span>");
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_morae_01() {
+        assert_eq!(vec!["た", "べ", "た"], split_morae("たべた"));
+    }
+
+    #[test]
+    fn split_morae_02() {
+        // Youon digraph and chōonpu each count as one mora.
+        assert_eq!(vec!["きょ", "う", "とー"], split_morae("きょうとー"));
+    }
+
+    #[test]
+    fn wrap_pitch_accent_01() {
+        // Heiban (n = 0): mora 1 is flat, the rest are accented.
+        let morae = split_morae("さくら");
+        assert_eq!(
+            "<span class=\"pitch_flat\">さ</span><span class=\"pitch_accent\">く</span><span class=\"pitch_accent\">ら</span>",
+            wrap_pitch_accent(&morae, Some(0))
+        );
+    }
+
+    #[test]
+    fn wrap_pitch_accent_02() {
+        // Accented (n = 2): morae 1..2 are accented, the rest are flat.
+        let morae = split_morae("はなし");
+        assert_eq!(
+            "<span class=\"pitch_accent\">は</span><span class=\"pitch_accent\">な</span><span class=\"pitch_flat\">し</span>",
+            wrap_pitch_accent(&morae, Some(2))
+        );
+    }
+
+    #[test]
+    fn wrap_pitch_accent_none() {
+        let morae = split_morae("たべた");
+        assert_eq!("たべた", wrap_pitch_accent(&morae, None));
+    }
+}
@@ -0,0 +1,240 @@
+//! Kana -> romaji (Hepburn) conversion, for rendering ruby text as romaji
+//! instead of kana.
+
+/// Converts a string of kana to romaji, using a pragmatic subset of
+/// Hepburn romanization: long vowels are handled via `ー`, the sokuon
+/// `っ` doubles the consonant of the following mora, and `ん` renders as
+/// `n` except before `b`/`m`/`p`, where it renders as `m`.
+///
+/// Characters that aren't kana are passed through unchanged.
+pub fn kana_to_romaji(text: &str) -> String {
+    let chars: Vec<char> = text
+        .chars()
+        .map(|c| super::katakana_to_hiragana(c).unwrap_or(c))
+        .collect();
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            'っ' => {
+                // Sokuon: doubles the consonant that starts the next mora.
+                if let Some(consonant) = next_consonant(&chars, i + 1) {
+                    out.push(consonant);
+                }
+                i += 1;
+            }
+
+            'ー' => {
+                // Chōonpu: extends the preceding vowel.
+                if let Some(v) = out.chars().last() {
+                    out.push(v);
+                }
+                i += 1;
+            }
+
+            'ん' => {
+                out.push(match next_consonant(&chars, i + 1) {
+                    Some('b') | Some('m') | Some('p') => 'm',
+                    _ => 'n',
+                });
+                i += 1;
+            }
+
+            _ => {
+                if let Some((consumed, romaji)) = chars.get(i..).and_then(mora) {
+                    out.push_str(romaji);
+                    i += consumed;
+                } else {
+                    out.push(chars[i]);
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// The first letter of the romaji rendering of the mora starting at
+/// `chars[from]`, if it's a consonant. Used to double consonants after
+/// `っ` and to pick `ん`'s `n`/`m` form.
+fn next_consonant(chars: &[char], from: usize) -> Option<char> {
+    chars
+        .get(from..)
+        .and_then(mora)
+        .and_then(|(_, romaji)| romaji.chars().next())
+        .filter(|c| !"aiueo".contains(*c))
+}
+
+/// Matches the longest mora (a youon digraph, or a single kana) at the
+/// start of `chars`, returning how many kana it consumed and its romaji
+/// spelling.
+fn mora(chars: &[char]) -> Option<(usize, &'static str)> {
+    if chars.len() >= 2 {
+        if let Some(r) = digraph(chars[0], chars[1]) {
+            return Some((2, r));
+        }
+    }
+    single(*chars.first()?).map(|r| (1, r))
+}
+
+fn digraph(a: char, b: char) -> Option<&'static str> {
+    Some(match (a, b) {
+        ('き', 'ゃ') => "kya",
+        ('き', 'ゅ') => "kyu",
+        ('き', 'ょ') => "kyo",
+        ('し', 'ゃ') => "sha",
+        ('し', 'ゅ') => "shu",
+        ('し', 'ょ') => "sho",
+        ('ち', 'ゃ') => "cha",
+        ('ち', 'ゅ') => "chu",
+        ('ち', 'ょ') => "cho",
+        ('に', 'ゃ') => "nya",
+        ('に', 'ゅ') => "nyu",
+        ('に', 'ょ') => "nyo",
+        ('ひ', 'ゃ') => "hya",
+        ('ひ', 'ゅ') => "hyu",
+        ('ひ', 'ょ') => "hyo",
+        ('み', 'ゃ') => "mya",
+        ('み', 'ゅ') => "myu",
+        ('み', 'ょ') => "myo",
+        ('り', 'ゃ') => "rya",
+        ('り', 'ゅ') => "ryu",
+        ('り', 'ょ') => "ryo",
+        ('ぎ', 'ゃ') => "gya",
+        ('ぎ', 'ゅ') => "gyu",
+        ('ぎ', 'ょ') => "gyo",
+        ('じ', 'ゃ') => "ja",
+        ('じ', 'ゅ') => "ju",
+        ('じ', 'ょ') => "jo",
+        ('ぢ', 'ゃ') => "ja",
+        ('ぢ', 'ゅ') => "ju",
+        ('ぢ', 'ょ') => "jo",
+        ('び', 'ゃ') => "bya",
+        ('び', 'ゅ') => "byu",
+        ('び', 'ょ') => "byo",
+        ('ぴ', 'ゃ') => "pya",
+        ('ぴ', 'ゅ') => "pyu",
+        ('ぴ', 'ょ') => "pyo",
+        _ => return None,
+    })
+}
+
+fn single(c: char) -> Option<&'static str> {
+    Some(match c {
+        'あ' => "a",
+        'い' => "i",
+        'う' => "u",
+        'え' => "e",
+        'お' => "o",
+        'か' => "ka",
+        'き' => "ki",
+        'く' => "ku",
+        'け' => "ke",
+        'こ' => "ko",
+        'さ' => "sa",
+        'し' => "shi",
+        'す' => "su",
+        'せ' => "se",
+        'そ' => "so",
+        'た' => "ta",
+        'ち' => "chi",
+        'つ' => "tsu",
+        'て' => "te",
+        'と' => "to",
+        'な' => "na",
+        'に' => "ni",
+        'ぬ' => "nu",
+        'ね' => "ne",
+        'の' => "no",
+        'は' => "ha",
+        'ひ' => "hi",
+        'ふ' => "fu",
+        'へ' => "he",
+        'ほ' => "ho",
+        'ま' => "ma",
+        'み' => "mi",
+        'む' => "mu",
+        'め' => "me",
+        'も' => "mo",
+        'や' => "ya",
+        'ゆ' => "yu",
+        'よ' => "yo",
+        'ら' => "ra",
+        'り' => "ri",
+        'る' => "ru",
+        'れ' => "re",
+        'ろ' => "ro",
+        'わ' => "wa",
+        'ゐ' => "i",
+        'ゑ' => "e",
+        'を' => "o",
+        'が' => "ga",
+        'ぎ' => "gi",
+        'ぐ' => "gu",
+        'げ' => "ge",
+        'ご' => "go",
+        'ざ' => "za",
+        'じ' => "ji",
+        'ず' => "zu",
+        'ぜ' => "ze",
+        'ぞ' => "zo",
+        'だ' => "da",
+        'ぢ' => "ji",
+        'づ' => "zu",
+        'で' => "de",
+        'ど' => "do",
+        'ば' => "ba",
+        'び' => "bi",
+        'ぶ' => "bu",
+        'べ' => "be",
+        'ぼ' => "bo",
+        'ぱ' => "pa",
+        'ぴ' => "pi",
+        'ぷ' => "pu",
+        'ぺ' => "pe",
+        'ぽ' => "po",
+        'ぁ' => "a",
+        'ぃ' => "i",
+        'ぅ' => "u",
+        'ぇ' => "e",
+        'ぉ' => "o",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kana_to_romaji_01() {
+        assert_eq!("tabeta", kana_to_romaji("たべた"));
+    }
+
+    #[test]
+    fn kana_to_romaji_02() {
+        // Katakana input, long vowel.
+        assert_eq!("tabeteeru", kana_to_romaji("タベテール"));
+    }
+
+    #[test]
+    fn kana_to_romaji_03() {
+        // Sokuon geminates the following consonant.
+        assert_eq!("gakkou", kana_to_romaji("がっこう"));
+    }
+
+    #[test]
+    fn kana_to_romaji_04() {
+        // ん as n vs m depending on what follows.
+        assert_eq!("shimbun", kana_to_romaji("しんぶん"));
+        assert_eq!("sampo", kana_to_romaji("さんぽ"));
+    }
+
+    #[test]
+    fn kana_to_romaji_05() {
+        // Youon digraphs.
+        assert_eq!("kyoutoshite", kana_to_romaji("きょうとして"));
+    }
+}